@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Weak};
+
+use tokio::sync::{broadcast, Mutex};
+
+// Single-flight coalescing for expensive, idempotent work keyed by `K`.
+//
+// Concurrent callers sharing a key all wait on the same in-progress
+// computation instead of redoing it: the first caller to arrive performs the
+// work and broadcasts the result to everyone else who joined in the
+// meantime. The entry is removed from the map as soon as the work finishes,
+// so a fresh call always starts a fresh computation rather than replaying a
+// (possibly stale, possibly failed) result.
+pub struct InFlight<K, V> {
+    pending: Mutex<HashMap<K, Weak<broadcast::Sender<Arc<V>>>>>,
+}
+
+impl<K, V> InFlight<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Runs `work` at most once per live `key`. Callers that arrive while a
+    // computation for `key` is already running subscribe to its result
+    // instead of invoking `work` themselves.
+    //
+    // The caller that starts the computation runs it (and the bookkeeping
+    // that follows it) on a detached `tokio::spawn` task rather than inline
+    // in this future. That makes the coalesced computation immune to that
+    // caller's own cancellation: routes are wrapped in a request timeout, so
+    // the caller that happens to start the work can have its own connection
+    // dropped mid-`work()` (a slow write queued behind a concurrency limit,
+    // for instance). If that dropped the work itself, it would drop the one
+    // strong `Sender` before it ever broadcasts, and every other coalesced
+    // waiter would see the channel close. Running it detached means the
+    // caller awaits the task's `JoinHandle` instead of the work future
+    // itself, so dropping that await never touches the task.
+    pub async fn run<F, Fut>(self: &Arc<Self>, key: K, work: F) -> Arc<V>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        loop {
+            let mut guard = self.pending.lock().await;
+            if let Some(sender) = guard.get(&key).and_then(Weak::upgrade) {
+                let mut receiver = sender.subscribe();
+                drop(guard);
+                match receiver.recv().await {
+                    Ok(result) => return result,
+                    // The caller driving this key had its own task panic
+                    // before broadcasting a result. Retry as if we were the
+                    // first caller -- with our own `work`, never used yet --
+                    // rather than propagating a spurious error to every
+                    // waiter that merely subscribed.
+                    Err(_) => continue,
+                }
+            }
+
+            let (sender, _) = broadcast::channel(1);
+            let sender = Arc::new(sender);
+            guard.insert(key.clone(), Arc::downgrade(&sender));
+            drop(guard);
+
+            let this = Arc::clone(self);
+            let key_for_task = key.clone();
+            let handle = tokio::spawn(async move {
+                let result = Arc::new(work().await);
+
+                // Drop the entry before broadcasting so nobody can subscribe
+                // to a channel that has already fired (and would therefore
+                // hang), and so an error result is never replayed to a
+                // later, unrelated caller.
+                this.pending.lock().await.remove(&key_for_task);
+                let _ = sender.send(Arc::clone(&result));
+                result
+            });
+
+            // `work` was already consumed by the task above, so there is no
+            // "retry" for this call: a panic inside it is re-raised here
+            // exactly as it would be had we awaited `work()` directly.
+            return match handle.await {
+                Ok(result) => result,
+                Err(join_err) => std::panic::resume_unwind(join_err.into_panic()),
+            };
+        }
+    }
+}
+
+impl<K, V> Default for InFlight<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use tokio::sync::Barrier;
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_result() {
+        let inflight = Arc::new(InFlight::<&'static str, usize>::new());
+        let invocations = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(4));
+
+        let mut handles = Vec::new();
+        for _ in 0..4 {
+            let inflight = inflight.clone();
+            let invocations = invocations.clone();
+            let barrier = barrier.clone();
+            handles.push(tokio::spawn(async move {
+                barrier.wait().await;
+                inflight
+                    .run("key", || async move {
+                        invocations.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        42
+                    })
+                    .await
+            }));
+        }
+
+        for handle in handles {
+            assert_eq!(*handle.await.unwrap(), 42);
+        }
+        assert_eq!(invocations.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn an_error_result_is_not_cached_for_the_next_call() {
+        let inflight = Arc::new(InFlight::<&'static str, Result<(), &'static str>>::new());
+
+        let first = inflight.run("key", || async { Err("boom") }).await;
+        assert_eq!(*first, Err("boom"));
+
+        let second = inflight.run("key", || async { Ok(()) }).await;
+        assert_eq!(*second, Ok(()));
+    }
+}
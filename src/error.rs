@@ -0,0 +1,111 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use tower_http::request_id::RequestId;
+
+use crate::store::StoreError;
+
+// What went wrong, independent of which request triggered it.
+#[derive(Debug, Clone)]
+enum AppErrorKind {
+    SchemaParse(String),
+    Io(String),
+    AvroEncode(String),
+    AvroDecode(String),
+    JsonSerialize(String),
+}
+
+// The error type returned by handlers. Carries the id of the request that
+// produced it, so a client (or whoever is reading the logs) can correlate a
+// failure response with its trace.
+#[derive(Debug, Clone)]
+pub struct AppError {
+    kind: AppErrorKind,
+    request_id: Option<String>,
+}
+
+impl AppError {
+    // Tags this error with the id of the request that triggered it, so it
+    // can be surfaced in the response body.
+    pub fn with_request_id(mut self, request_id: &RequestId) -> Self {
+        self.request_id = request_id.header_value().to_str().ok().map(str::to_owned);
+        self
+    }
+
+    fn status(&self) -> StatusCode {
+        match self.kind {
+            // The stored schema doesn't parse, or the stored record doesn't
+            // decode against it: the data on disk, not the server, is at fault.
+            AppErrorKind::SchemaParse(_) | AppErrorKind::AvroDecode(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            AppErrorKind::Io(_) | AppErrorKind::AvroEncode(_) | AppErrorKind::JsonSerialize(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn message(&self) -> &str {
+        match &self.kind {
+            AppErrorKind::SchemaParse(msg)
+            | AppErrorKind::Io(msg)
+            | AppErrorKind::AvroEncode(msg)
+            | AppErrorKind::AvroDecode(msg)
+            | AppErrorKind::JsonSerialize(msg) => msg,
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        Self {
+            kind: AppErrorKind::Io(err.to_string()),
+            request_id: None,
+        }
+    }
+}
+
+impl From<apache_avro::Error> for AppError {
+    fn from(err: apache_avro::Error) -> Self {
+        Self {
+            kind: AppErrorKind::AvroDecode(err.to_string()),
+            request_id: None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        Self {
+            kind: AppErrorKind::JsonSerialize(err.to_string()),
+            request_id: None,
+        }
+    }
+}
+
+impl From<StoreError> for AppError {
+    fn from(err: StoreError) -> Self {
+        let kind = match err {
+            StoreError::Io(err) => AppErrorKind::Io(err.to_string()),
+            StoreError::SchemaParse(err) => AppErrorKind::SchemaParse(err.to_string()),
+            StoreError::AvroEncode(err) => AppErrorKind::AvroEncode(err.to_string()),
+            StoreError::AvroDecode(err) => AppErrorKind::AvroDecode(err.to_string()),
+        };
+        Self {
+            kind,
+            request_id: None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = Json(json!({
+            "error": self.message(),
+            "request_id": self.request_id,
+        }));
+        (status, body).into_response()
+    }
+}
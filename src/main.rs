@@ -1,24 +1,42 @@
+mod coalesce;
+mod error;
+mod openapi;
+mod schema_watcher;
+mod store;
+
 use axum::error_handling::HandleErrorLayer;
+use axum::extract::{Extension, State};
 use axum::http::{Request, StatusCode};
 use axum::response::IntoResponse;
-use axum::routing::{get, post};
-use axum::{BoxError, Json, Router, Server};
+use axum::routing::get;
+use axum::{BoxError, Json};
+use coalesce::InFlight;
+use error::AppError;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fs::{read_to_string, write};
 use std::net::SocketAddr;
-use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
+use store::{FsRecordStore, RecordStore};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
 use tokio::time::sleep;
 use tower::ServiceBuilder;
 use tower_http::request_id::{MakeRequestId, RequestId};
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tower_http::ServiceBuilderExt;
-use tracing::info;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use tracing::instrument;
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 use ulid::Ulid;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+// How long to wait, on shutdown, for writes that are still in flight before
+// giving up on a clean drain.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
 
 // This struct implements the trait needed to associate a request with a UUID
 
@@ -34,53 +52,199 @@ impl MakeRequestId for MakeRequestUlid {
 
 // This struct describes the shape of the JSON that can be posted to our app.
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct Input {
     content: String,
 }
 
 // This struct describes the shape of the AVRO file our app manage and the shape of the JSON output.
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
 pub struct Record {
     content: String,
 }
 
+// The download path is keyed on the store's current version, so two
+// requests only share a result if they would have observed the same data.
+type DownloadKey = String;
+
+// The upload path is keyed on the posted content, so concurrent uploads of
+// the same payload perform a single write instead of racing each other.
+type UploadKey = String;
+
+// Shared application state, injected into handlers via `State`.
+//
+// Both in-flight registries use `Result` values so that a failure is
+// broadcast to every waiter but never cached: a transient I/O error on one
+// request shouldn't poison the next.
+#[derive(Clone)]
+pub struct AppState {
+    record_store: Arc<dyn RecordStore>,
+    download_inflight: Arc<InFlight<DownloadKey, Result<Value, AppError>>>,
+    upload_inflight: Arc<InFlight<UploadKey, Result<(), AppError>>>,
+    // Cancelled once a shutdown signal is received, so handlers and
+    // background tasks (e.g. the schema watcher) can stop new work.
+    shutdown: CancellationToken,
+    // Tracks writes that have been handed off to `tokio::spawn` so they keep
+    // running (and `record.avro` is never left truncated) even if the
+    // request that triggered them is dropped when its connection closes.
+    // Drained with a timeout on shutdown.
+    writes_in_flight: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl AppState {
+    fn new(record_store: Arc<dyn RecordStore>, shutdown: CancellationToken) -> Self {
+        Self {
+            record_store,
+            download_inflight: Arc::new(InFlight::new()),
+            upload_inflight: Arc::new(InFlight::new()),
+            shutdown,
+            writes_in_flight: Arc::new(Mutex::new(JoinSet::new())),
+        }
+    }
+}
+
 // This handler saves a JSON into an AVRO file.
+//
+// Concurrent uploads of identical content are coalesced into a single write
+// via `AppState::upload_inflight`.
 
-#[instrument]
-async fn json_to_avro(Json(input): Json<Input>) -> impl IntoResponse {
-    let output = Record {
-        content: input.content,
-    };
-    let file_content = read_to_string(Path::new("./schema.avro")).unwrap();
-    let schema = apache_avro::Schema::parse_str(&file_content).unwrap();
-    let mut writer = apache_avro::Writer::new(&schema, Vec::new());
-    writer.append_ser(output).unwrap();
-    write("./record.avro", &writer.into_inner().unwrap()).unwrap();
+#[utoipa::path(
+    post,
+    path = "/upload",
+    request_body = Input,
+    responses(
+        (status = 204, description = "The record was stored"),
+        (status = 422, description = "The schema failed to parse"),
+        (status = 500, description = "I/O or Avro encoding failure"),
+    ),
+    tag = "records",
+)]
+#[instrument(skip(state, request_id))]
+async fn json_to_avro(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(input): Json<Input>,
+) -> Result<impl IntoResponse, AppError> {
+    if state.shutdown.is_cancelled() {
+        let err = std::io::Error::other("server is shutting down");
+        return Err(AppError::from(err).with_request_id(&request_id));
+    }
+    let key = input.content.clone();
+    let record_store = state.record_store.clone();
+    let writes_in_flight = state.writes_in_flight.clone();
+    let result = state
+        .upload_inflight
+        .run(key, || async move {
+            let record = Record {
+                content: input.content,
+            };
+            write_tracked(&writes_in_flight, record_store, record).await
+        })
+        .await;
+    result
+        .as_ref()
+        .clone()
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(|err| err.with_request_id(&request_id))
+}
+
+// Runs the write on a detached task tracked in `writes_in_flight`, so that if
+// the request's own connection is dropped mid-write (e.g. because the
+// client disconnected, or the server is draining for shutdown), the write
+// itself still runs to completion rather than leaving `record.avro`
+// truncated.
+async fn write_tracked(
+    writes_in_flight: &Mutex<JoinSet<()>>,
+    record_store: Arc<dyn RecordStore>,
+    record: Record,
+) -> Result<(), AppError> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    writes_in_flight.lock().await.spawn(async move {
+        let result = record_store.write(&record).await.map_err(AppError::from);
+        let _ = tx.send(result);
+    });
+    rx.await.unwrap_or_else(|_| {
+        Err(AppError::from(std::io::Error::other(
+            "write task was dropped before sending its result",
+        )))
+    })
 }
 
 // This handler read the AVRO file and return its content as JSON.
+//
+// Concurrent reads of the same underlying data are coalesced into a single
+// read-and-decode via `AppState::download_inflight`.
 
-#[instrument]
-async fn avro_to_json() -> Json<Value> {
-    let schema = read_to_string(Path::new("./schema.avro")).unwrap();
-    let schema = apache_avro::Schema::parse_str(&schema).unwrap();
-    let record = std::fs::read(Path::new("./record.avro")).unwrap();
-    let reader = apache_avro::Reader::with_schema(&schema, &record[..]).unwrap();
-    let data = reader
-        .map(|record| apache_avro::from_value::<Record>(&record.unwrap()).unwrap())
-        .map(|output| serde_json::to_value(&output).unwrap())
-        .collect::<Value>();
-    Json(data)
+#[utoipa::path(
+    get,
+    path = "/download",
+    responses(
+        (status = 200, description = "The stored records", body = [Record]),
+        (status = 422, description = "The schema or record failed to decode"),
+        (status = 500, description = "I/O or Avro decoding failure"),
+    ),
+    tag = "records",
+)]
+#[instrument(skip(state, request_id))]
+async fn avro_to_json(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+) -> Result<impl IntoResponse, AppError> {
+    let key = state
+        .record_store
+        .version()
+        .await
+        .map_err(|err| AppError::from(err).with_request_id(&request_id))?;
+    let record_store = state.record_store.clone();
+    let result = state
+        .download_inflight
+        .run(key, || async move {
+            let records = record_store.read().await.map_err(AppError::from)?;
+            serde_json::to_value(&records).map_err(AppError::from)
+        })
+        .await;
+    result
+        .as_ref()
+        .clone()
+        .map(Json)
+        .map_err(|err| err.with_request_id(&request_id))
 }
 
-// This function will complete when Ctrl-C is pressed and the platform signal is sent to the app.
-// We use it as an example of handling graceful shutdown.
+// This function completes when either Ctrl-C or SIGTERM is received, and
+// cancels `shutdown` so the rest of the app can react. `with_graceful_shutdown`
+// uses it to stop accepting new connections; handlers and background tasks
+// use the cancelled token to stop starting new work.
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install the Ctrl-C handler");
+    };
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    tokio::select! {
+        _ = ctrl_c => info!("Ctrl-C received: gracefully shutting down..."),
+        _ = terminate => info!("SIGTERM received: gracefully shutting down..."),
+    }
+    shutdown.cancel();
+}
 
-async fn shutdown_signal() {
-    tokio::signal::ctrl_c().await.unwrap();
-    info!("Ctr-C received: gracefully shutting down...");
+// Waits for every write that's still running to finish, up to `timeout`.
+// Called after the server has stopped accepting connections, so a dropped
+// client never leaves `record.avro` truncated.
+async fn drain_writes(writes_in_flight: &Mutex<JoinSet<()>>, timeout: Duration) {
+    let drain = async {
+        let mut writes_in_flight = writes_in_flight.lock().await;
+        while writes_in_flight.join_next().await.is_some() {}
+    };
+    if tokio::time::timeout(timeout, drain).await.is_err() {
+        warn!("timed out waiting for in-flight writes to drain");
+    }
 }
 
 // This function will handle the application errors and converting them to HTTP status codes.
@@ -108,8 +272,40 @@ async fn main() {
         .with_span_events(tracing_subscriber::fmt::format::FmtSpan::ACTIVE)
         .init();
 
+    // Initialize the shared application state. The schema and record paths
+    // are owned by the store, not the handlers, so a different `RecordStore`
+    // implementation can be swapped in without touching handler code.
+    let record_store = FsRecordStore::new("./schema.avro", "./record.avro")
+        .expect("./schema.avro must exist and parse at startup");
+    let (schema, schema_generation) = record_store.schema_handles();
+    let record_store = Arc::new(record_store);
+    let shutdown = CancellationToken::new();
+    let state = AppState::new(record_store, shutdown.clone());
+
+    // Watch the schema file for changes so it can be hot-reloaded without a
+    // restart. The watcher is kept alive by binding it here; dropping it
+    // would stop the watch.
+    let _schema_watcher = schema_watcher::watch(
+        "./schema.avro",
+        schema,
+        schema_generation,
+        shutdown.clone(),
+    )
+    .expect("failed to start schema watcher");
+
+    // Build the router for the upload/download endpoints via `OpenApiRouter`
+    // so the OpenAPI document served below is generated from these routes
+    // themselves, instead of a hand-maintained list: a route added here is
+    // automatically a route `openapi::openapi` describes.
+    let (router, api) = OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        // Given a compliant JSON file store it in a binary avro file.
+        // Send back the avro file deserializing it to JSON.
+        .routes(routes!(json_to_avro, avro_to_json))
+        .with_state(state.clone())
+        .split_for_parts();
+
     // Initialize the router and the application.
-    let router = Router::new()
+    let router = router
         // Return an empty 200.
         .route("/healthcheck", get(|| async {}))
         // Return a 408 after 10 seconds.
@@ -125,10 +321,10 @@ async fn main() {
                 sleep(Duration::from_secs(5)).await;
             }),
         )
-        // Given a compliant JSON file store it in a binary avro file.
-        .route("/upload", post(json_to_avro))
-        // Send back the avro file deserializing it to JSON.
-        .route("/download", get(avro_to_json))
+        // Serve the OpenAPI document generated from the routes above.
+        .route("/openapi.json", get(openapi::openapi))
+        // Serve the plugin manifest so LLM tool platforms can discover this service.
+        .route("/.well-known/ai-plugin.json", get(openapi::manifest))
         .layer(
             ServiceBuilder::new()
                 // We inject the error handler.
@@ -146,12 +342,20 @@ async fn main() {
                 )
                 // This layer propagate the ULID to the response headers.
                 .propagate_x_request_id(),
-        );
+        )
+        // Hands the generated OpenAPI document to the `/openapi.json` handler.
+        .layer(Extension(api));
 
-    Server::bind(&SocketAddr::from(([127, 0, 0, 1], 3000)))
-        .serve(router.into_make_service())
-        // We inject the Ctrl-C handling function using it for graceful shutdown
-        .with_graceful_shutdown(shutdown_signal())
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 3000)))
+        .await
+        .unwrap();
+    axum::serve(listener, router.into_make_service())
+        // Stop accepting connections on Ctrl-C or SIGTERM.
+        .with_graceful_shutdown(shutdown_signal(shutdown))
         .await
         .unwrap();
+
+    // The server has stopped accepting connections; give any write that's
+    // still running a chance to finish before the process exits.
+    drain_writes(&state.writes_in_flight, DRAIN_TIMEOUT).await;
 }
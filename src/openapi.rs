@@ -0,0 +1,67 @@
+use axum::extract::Extension;
+use axum::Json;
+use serde::Serialize;
+use utoipa::OpenApi;
+
+// The base OpenAPI document: everything that isn't derived from the route
+// table itself. `main` builds an `OpenApiRouter` with this as its starting
+// point and merges in the paths for each registered handler via the
+// `utoipa_axum::routes!` macro, so the served document stays in sync with
+// the router as routes are added or removed -- nobody has to remember to
+// list a handler here by hand.
+#[derive(OpenApi)]
+#[openapi(
+    tags(
+        (name = "records", description = "Read and write the single Avro-backed record store")
+    )
+)]
+pub struct ApiDoc;
+
+// Serves the OpenAPI 3 document assembled by `main` from the live router.
+pub async fn openapi(
+    Extension(api): Extension<utoipa::openapi::OpenApi>,
+) -> Json<utoipa::openapi::OpenApi> {
+    Json(api)
+}
+
+// A minimal manifest describing this service, in the shape LLM tool
+// platforms expect when discovering a plugin backend.
+#[derive(Serialize)]
+pub struct PluginManifest {
+    schema_version: &'static str,
+    name_for_model: &'static str,
+    name_for_human: &'static str,
+    description_for_model: &'static str,
+    description_for_human: &'static str,
+    api: PluginApi,
+    contact_email: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct PluginApi {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    url: &'static str,
+}
+
+impl Default for PluginManifest {
+    fn default() -> Self {
+        Self {
+            schema_version: "v1",
+            name_for_model: "axum_playground",
+            name_for_human: "Axum Playground",
+            description_for_model: "Upload and download a single record through an Avro-backed store.",
+            description_for_human: "Upload and download a record backed by an Avro file.",
+            api: PluginApi {
+                kind: "openapi",
+                url: "/openapi.json",
+            },
+            contact_email: "dartheian@users.noreply.github.com",
+        }
+    }
+}
+
+// Serves the plugin manifest.
+pub async fn manifest() -> Json<PluginManifest> {
+    Json(PluginManifest::default())
+}
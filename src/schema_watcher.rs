@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+// Watches `schema_path` for changes and, on each one, parses the new
+// contents and swaps them into `schema` -- but only if parsing succeeds. A
+// malformed schema is logged and the previous one keeps serving requests.
+// The returned watcher must be kept alive for the duration of the watch; it
+// stops as soon as it is dropped. The background task also stops as soon as
+// `shutdown` is cancelled.
+//
+// The watch is registered on the *parent directory* rather than on
+// `schema_path` itself, and reacts to any event that touches it, not just
+// in-place modifications. Config files are commonly replaced by unlink+
+// rename (an editor's atomic save, `sed -i`, a ConfigMap symlink swap)
+// rather than written in place; a watch on the leaf path would follow the
+// old, now-unlinked inode and go silently dead the moment that happens.
+pub fn watch(
+    schema_path: impl AsRef<Path>,
+    schema: Arc<ArcSwap<apache_avro::Schema>>,
+    schema_generation: Arc<AtomicU64>,
+    shutdown: CancellationToken,
+) -> notify::Result<RecommendedWatcher> {
+    let schema_path = schema_path.as_ref().to_path_buf();
+    let parent = schema_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    let (tx, mut rx) = mpsc::channel(16);
+
+    // Compared by file name rather than the full path: we're watching the
+    // parent directory, and notify reports paths as it sees them on disk,
+    // which may not share `schema_path`'s exact (e.g. relative, `./`-
+    // prefixed) spelling.
+    let watched_name = schema_path.file_name().map(ToOwned::to_owned);
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            // The callback runs on notify's own thread, so hand the
+            // notification off to the async task below instead of doing the
+            // (blocking) reparse here. We watch the parent directory, so
+            // filter down to events that actually touch the schema file --
+            // any kind (create, modify, remove, rename-in/out) is a reason
+            // to attempt a reload.
+            Ok(event)
+                if event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == watched_name.as_deref()) =>
+            {
+                let _ = tx.blocking_send(());
+            }
+            Ok(_) => {}
+            Err(err) => warn!(%err, "schema watcher error"),
+        }
+    })?;
+    watcher.watch(parent, RecursiveMode::NonRecursive)?;
+
+    tokio::spawn(async move {
+        loop {
+            let notification = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                notification = rx.recv() => notification,
+            };
+            let Some(()) = notification else { break };
+
+            let raw = match tokio::fs::read_to_string(&schema_path).await {
+                Ok(raw) => raw,
+                Err(err) => {
+                    warn!(%err, "failed to read schema file after change notification");
+                    continue;
+                }
+            };
+            match apache_avro::Schema::parse_str(&raw) {
+                Ok(parsed) => {
+                    schema.store(Arc::new(parsed));
+                    schema_generation.fetch_add(1, Ordering::SeqCst);
+                    info!("schema reloaded");
+                }
+                Err(err) => warn!(%err, "new schema failed to parse, keeping previous schema"),
+            }
+        }
+    });
+
+    Ok(watcher)
+}
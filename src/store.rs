@@ -0,0 +1,219 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+
+use crate::Record;
+
+// Abstracts over where records live and how they're (de)serialized, so the
+// handlers don't have to know about the filesystem or the Avro schema.
+#[async_trait]
+pub trait RecordStore: Send + Sync {
+    async fn read(&self) -> Result<Vec<Record>, StoreError>;
+    async fn write(&self, record: &Record) -> Result<(), StoreError>;
+
+    // An opaque token that changes whenever the underlying data changes.
+    // Used to key the request-coalescing cache without leaking
+    // backend-specific details (mtimes, revision numbers, ...) to callers.
+    async fn version(&self) -> Result<String, StoreError>;
+}
+
+// An error produced while reading or writing through a `RecordStore`.
+#[derive(Debug)]
+pub enum StoreError {
+    Io(std::io::Error),
+    SchemaParse(apache_avro::Error),
+    AvroEncode(apache_avro::Error),
+    AvroDecode(apache_avro::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(err) => write!(f, "record store I/O error: {err}"),
+            StoreError::SchemaParse(err) => write!(f, "failed to parse Avro schema: {err}"),
+            StoreError::AvroEncode(err) => write!(f, "failed to encode record as Avro: {err}"),
+            StoreError::AvroDecode(err) => write!(f, "failed to decode record from Avro: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+// A `RecordStore` backed by an Avro schema file and an Avro record file on
+// the local filesystem. The schema is parsed once and then kept in an
+// `ArcSwap`, refreshed in the background by `schema_watcher::watch`, so a
+// request never has to parse it from disk.
+pub struct FsRecordStore {
+    schema: Arc<ArcSwap<apache_avro::Schema>>,
+    // Bumped every time the watched schema file is successfully reparsed.
+    // Combined with the record's mtime to key the download coalescing cache.
+    schema_generation: Arc<AtomicU64>,
+    record_path: PathBuf,
+}
+
+impl FsRecordStore {
+    // Reads and parses `schema_path` once up front, so construction fails
+    // fast if the schema on disk is missing or malformed.
+    pub fn new(
+        schema_path: impl Into<PathBuf>,
+        record_path: impl Into<PathBuf>,
+    ) -> Result<Self, StoreError> {
+        let schema_path = schema_path.into();
+        let schema = parse_schema_file(&schema_path)?;
+        Ok(Self {
+            schema: Arc::new(ArcSwap::from_pointee(schema)),
+            schema_generation: Arc::new(AtomicU64::new(0)),
+            record_path: record_path.into(),
+        })
+    }
+
+    // Handles shared with `schema_watcher::watch`, which swaps in a freshly
+    // parsed schema (and bumps the generation counter) whenever the schema
+    // file changes on disk.
+    pub fn schema_handles(&self) -> (Arc<ArcSwap<apache_avro::Schema>>, Arc<AtomicU64>) {
+        (self.schema.clone(), self.schema_generation.clone())
+    }
+}
+
+fn parse_schema_file(schema_path: &PathBuf) -> Result<apache_avro::Schema, StoreError> {
+    let raw = std::fs::read_to_string(schema_path).map_err(StoreError::Io)?;
+    apache_avro::Schema::parse_str(&raw).map_err(StoreError::SchemaParse)
+}
+
+// Writes `bytes` to a temporary file alongside `path` and renames it into
+// place, so a reader never observes a truncated file and a process killed
+// mid-write (SIGKILL, OOM, a shutdown that outraces `drain_writes`) leaves
+// the previous, still-complete file behind instead of a corrupt one.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+    let tmp_path = path.with_extension(format!(
+        "tmp.{}.{}",
+        std::process::id(),
+        TMP_SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+#[async_trait]
+impl RecordStore for FsRecordStore {
+    async fn read(&self) -> Result<Vec<Record>, StoreError> {
+        let schema = self.schema.load();
+        let bytes = std::fs::read(&self.record_path).map_err(StoreError::Io)?;
+        let reader =
+            apache_avro::Reader::with_schema(&schema, &bytes[..]).map_err(StoreError::AvroDecode)?;
+        reader
+            .map(|value| {
+                let value = value.map_err(StoreError::AvroDecode)?;
+                apache_avro::from_value::<Record>(&value).map_err(StoreError::AvroDecode)
+            })
+            .collect()
+    }
+
+    async fn write(&self, record: &Record) -> Result<(), StoreError> {
+        let schema = self.schema.load();
+        let mut writer = apache_avro::Writer::new(&schema, Vec::new());
+        writer
+            .append_ser(record)
+            .map_err(StoreError::AvroEncode)?;
+        let bytes = writer.into_inner().map_err(StoreError::AvroEncode)?;
+        write_atomic(&self.record_path, &bytes).map_err(StoreError::Io)
+    }
+
+    async fn version(&self) -> Result<String, StoreError> {
+        let schema_generation = self.schema_generation.load(Ordering::SeqCst);
+        let record_mtime = std::fs::metadata(&self.record_path)
+            .and_then(|m| m.modified())
+            .map_err(StoreError::Io)?;
+        Ok(format!("{schema_generation}-{record_mtime:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    // An in-memory `RecordStore`, standing in for the filesystem so handler
+    // logic can be exercised without touching disk.
+    #[derive(Default)]
+    pub struct InMemoryRecordStore {
+        records: StdMutex<Vec<Record>>,
+        version: AtomicU64,
+    }
+
+    #[async_trait]
+    impl RecordStore for InMemoryRecordStore {
+        async fn read(&self) -> Result<Vec<Record>, StoreError> {
+            Ok(self.records.lock().unwrap().clone())
+        }
+
+        async fn write(&self, record: &Record) -> Result<(), StoreError> {
+            self.records.lock().unwrap().push(record.clone());
+            self.version.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn version(&self) -> Result<String, StoreError> {
+            Ok(self.version.load(Ordering::SeqCst).to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_roundtrips_a_write() {
+        let store = InMemoryRecordStore::default();
+        let record = Record {
+            content: "hello".to_owned(),
+        };
+
+        store.write(&record).await.unwrap();
+
+        assert_eq!(store.read().await.unwrap(), vec![record]);
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_bumps_version_on_write() {
+        let store = InMemoryRecordStore::default();
+        let before = store.version().await.unwrap();
+
+        store
+            .write(&Record {
+                content: "hello".to_owned(),
+            })
+            .await
+            .unwrap();
+
+        assert_ne!(before, store.version().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn fs_record_store_roundtrips_a_write() {
+        let dir = std::env::temp_dir().join(format!(
+            "axum-playground-test-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let schema_path = dir.join("schema.avro");
+        let record_path = dir.join("record.avro");
+        std::fs::write(
+            &schema_path,
+            r#"{"type": "record", "name": "Record", "fields": [{"name": "content", "type": "string"}]}"#,
+        )
+        .unwrap();
+
+        let store = FsRecordStore::new(&schema_path, &record_path).unwrap();
+        let record = Record {
+            content: "hello".to_owned(),
+        };
+        store.write(&record).await.unwrap();
+
+        assert_eq!(store.read().await.unwrap(), vec![record]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}